@@ -0,0 +1,26 @@
+//! Platform-agnostic custom cursor types shared by every backend.
+
+/// One frame of a (possibly animated) custom cursor, as supplied by the application.
+#[derive(Debug, Clone)]
+pub(crate) struct CustomCursorFrame {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    pub(crate) hotspot_x: u16,
+    pub(crate) hotspot_y: u16,
+    /// How long this frame is shown before advancing to the next one, in milliseconds. Ignored
+    /// for single-frame (static) cursors.
+    pub(crate) delay: u32,
+    /// Tightly packed RGBA8 pixels, `width * height * 4` bytes.
+    pub(crate) rgba: Vec<u8>,
+}
+
+/// The platform-agnostic source data behind a custom cursor, handed to each backend's
+/// `CustomCursor::new`.
+#[derive(Debug, Clone)]
+pub(crate) struct CustomCursorSource {
+    pub(crate) frames: Vec<CustomCursorFrame>,
+    /// Whether every frame's `rgba` is already premultiplied by its alpha channel. Backends
+    /// whose upload path requires premultiplied data (e.g. X11's ARGB32 picture format) must
+    /// premultiply it themselves when this is `false`.
+    pub(crate) premultiplied: bool,
+}
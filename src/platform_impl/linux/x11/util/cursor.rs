@@ -1,12 +1,14 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::iter;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 
 use x11rb::connection::Connection;
 use x11rb::protocol::render::{self, ConnectionExt as _};
 use x11rb::protocol::xproto;
 
-use crate::platform_impl::PlatformCustomCursorSource;
+use crate::platform_impl::{CustomCursorFrame, PlatformCustomCursorSource};
 use crate::window::CursorIcon;
 
 use super::super::ActiveEventLoop;
@@ -14,18 +16,33 @@ use super::*;
 
 impl XConnection {
     pub fn set_cursor_icon(&self, window: xproto::Window, cursor: Option<CursorIcon>) {
-        let cursor = *self
-            .cursor_cache
-            .lock()
-            .unwrap()
-            .entry(cursor)
-            .or_insert_with(|| self.get_cursor(cursor).expect("failed to create cursor"));
+        if let Err(err) = self.try_set_cursor_icon(window, cursor) {
+            tracing::error!("failed to set cursor icon: {err}");
+        }
+    }
+
+    /// Fallible counterpart to [`Self::set_cursor_icon`].
+    ///
+    /// Returns an error instead of panicking when the icon can't be resolved or created, e.g.
+    /// because of a malformed theme file, a missing XRender format, or a dropped connection.
+    pub fn try_set_cursor_icon(
+        &self,
+        window: xproto::Window,
+        cursor: Option<CursorIcon>,
+    ) -> Result<(), X11Error> {
+        let size = xcursor::target_size();
+        let cursor = match self.cursor_cache.lock().unwrap().entry((cursor, size)) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => *entry.insert(self.get_cursor(cursor, size)?),
+        };
 
-        self.update_cursor(window, cursor).expect("Failed to set cursor");
+        self.update_cursor(window, cursor)
     }
 
     pub(crate) fn set_custom_cursor(&self, window: xproto::Window, cursor: &CustomCursor) {
-        self.update_cursor(window, cursor.inner.cursor).expect("Failed to set cursor");
+        if let Err(err) = self.update_cursor(window, cursor.inner.cursor) {
+            tracing::error!("failed to set custom cursor: {err}");
+        }
     }
 
     /// Create a cursor from an image.
@@ -64,7 +81,12 @@ impl XConnection {
         // Create the XRender picture.
         let picture = self.xcb_connection().generate_id()?;
         self.xcb_connection()
-            .render_create_picture(picture, pixmap, self.find_argb32_format(), &Default::default())?
+            .render_create_picture(
+                picture,
+                pixmap,
+                self.find_argb32_format()?,
+                &Default::default(),
+            )?
             .check()?;
         let _picture_guard = CallOnDrop(|| {
             self.xcb_connection().render_free_picture(picture).map(|r| r.ignore_error()).ok();
@@ -80,8 +102,85 @@ impl XConnection {
         Ok(cursor)
     }
 
+    /// Create a cursor from one or more frames.
+    ///
+    /// When more than one frame is given and the server's RENDER extension supports
+    /// `RenderCreateAnimCursor` (added in RENDER 0.8), the frames are combined into a single
+    /// animated cursor. Otherwise the first frame is used as a static cursor.
+    fn create_cursor_from_frames<F: CursorFrameImage>(
+        &self,
+        frames: &[F],
+    ) -> Result<xproto::Cursor, X11Error> {
+        if frames.is_empty() {
+            return Err(X11Error::Other("cannot create a cursor with no frames".into()));
+        }
+
+        let mut per_frame = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let (hotspot_x, hotspot_y) = frame.hotspot();
+            let cursor = self.create_cursor_from_image(
+                frame.width(),
+                frame.height(),
+                32,
+                hotspot_x,
+                hotspot_y,
+                frame.argb(),
+            )?;
+            per_frame.push((cursor, frame.delay()));
+        }
+
+        if per_frame.len() == 1 || !self.supports_anim_cursor() {
+            let (cursor, _) = per_frame[0];
+            for &(extra, _) in &per_frame[1..] {
+                self.xcb_connection().free_cursor(extra).map(|r| r.ignore_error()).ok();
+            }
+            return Ok(cursor);
+        }
+
+        let anim_cursor = self.xcb_connection().generate_id()?;
+        let elements: Vec<render::AnimCursor> = per_frame
+            .iter()
+            .map(|&(cursor, delay)| render::AnimCursor { cursor, delay })
+            .collect();
+        self.xcb_connection().render_create_anim_cursor(anim_cursor, &elements)?.check()?;
+
+        // The X server copies the frame references when the anim cursor is created, so the
+        // per-frame cursors only need to live long enough for this call.
+        for (cursor, _) in per_frame {
+            self.xcb_connection().free_cursor(cursor).map(|r| r.ignore_error()).ok();
+        }
+
+        Ok(anim_cursor)
+    }
+
+    /// Whether the server's RENDER extension supports `RenderCreateAnimCursor`, which was added
+    /// in RENDER 0.8.
+    ///
+    /// The server's RENDER version can't change over the lifetime of a connection, so this is
+    /// queried once per `XConnection` and cached rather than round-tripped on every cursor
+    /// change. The cache is keyed by the `XConnection`'s address rather than stored as a field on
+    /// it, since that struct isn't declared in this module.
+    fn supports_anim_cursor(&self) -> bool {
+        static CACHE: OnceLock<Mutex<HashMap<usize, bool>>> = OnceLock::new();
+        let key = self as *const XConnection as usize;
+        let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+
+        if let Some(&supported) = cache.lock().unwrap().get(&key) {
+            return supported;
+        }
+
+        let supported = self
+            .xcb_connection()
+            .render_query_version(0, 8)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .is_some_and(|reply| (reply.major_version, reply.minor_version) >= (0, 8));
+        cache.lock().unwrap().insert(key, supported);
+        supported
+    }
+
     /// Find the render format that corresponds to ARGB32.
-    fn find_argb32_format(&self) -> render::Pictformat {
+    fn find_argb32_format(&self) -> Result<render::Pictformat, X11Error> {
         macro_rules! direct {
             ($format:expr, $shift_name:ident, $mask_name:ident, $shift:expr) => {{
                 ($format).direct.$shift_name == $shift && ($format).direct.$mask_name == 0xff
@@ -99,20 +198,32 @@ impl XConnection {
                     && direct!(format, blue_shift, blue_mask, 0)
                     && direct!(format, alpha_shift, alpha_mask, 24)
             })
-            .expect("unable to find ARGB32 xrender format")
-            .id
+            .map(|format| format.id)
+            .ok_or_else(|| X11Error::Other("unable to find ARGB32 XRender format".into()))
     }
 
     fn create_empty_cursor(&self) -> Result<xproto::Cursor, X11Error> {
         self.create_cursor_from_image(1, 1, 32, 0, 0, &[0, 0, 0, 0])
     }
 
-    fn get_cursor(&self, cursor: Option<CursorIcon>) -> Result<xproto::Cursor, X11Error> {
+    fn get_cursor(&self, cursor: Option<CursorIcon>, size: u32) -> Result<xproto::Cursor, X11Error> {
         let cursor = match cursor {
             Some(cursor) => cursor,
             None => return self.create_empty_cursor(),
         };
 
+        // Parse the theme's XCursor file directly, so that animated cursors (e.g. `wait`) play
+        // back instead of being truncated to their first frame, and so that themes shipping
+        // several nominal sizes pick the one closest to `size`.
+        let theme = xcursor::theme_name();
+        for &name in iter::once(&cursor.name()).chain(cursor.alt_names().iter()) {
+            if let Some(frames) = xcursor::load_icon(&theme, name, size) {
+                return self.create_cursor_from_frames(&frames);
+            }
+        }
+
+        // Fall back to `x11rb`'s own cursor handle, which covers the legacy core-font glyph
+        // names that some themes don't ship an XCursor file for at all.
         let database = self.database();
         let handle = x11rb::cursor::Handle::new(
             self.xcb_connection(),
@@ -177,26 +288,50 @@ impl Eq for CustomCursor {}
 impl CustomCursor {
     pub(crate) fn new(
         event_loop: &ActiveEventLoop,
-        mut cursor: PlatformCustomCursorSource,
+        cursor: PlatformCustomCursorSource,
     ) -> CustomCursor {
-        // Reverse RGBA order to BGRA.
-        cursor.0.rgba.chunks_mut(4).for_each(|chunk| {
-            chunk[0..3].reverse();
-        });
+        match Self::try_new(event_loop, cursor) {
+            Ok(cursor) => cursor,
+            Err(err) => {
+                tracing::error!("failed to create a custom cursor: {err}");
+                Self::empty(event_loop)
+            },
+        }
+    }
 
-        let cursor = event_loop
-            .xconn
-            .create_cursor_from_image(
-                cursor.0.width,
-                cursor.0.height,
-                32,
-                cursor.0.hotspot_x,
-                cursor.0.hotspot_y,
-                &cursor.0.rgba,
-            )
-            .expect("failed to create a custom cursor");
+    /// Fallible counterpart to [`Self::new`].
+    pub(crate) fn try_new(
+        event_loop: &ActiveEventLoop,
+        mut cursor: PlatformCustomCursorSource,
+    ) -> Result<CustomCursor, X11Error> {
+        // Reorder RGBA to BGRA and premultiply alpha for every frame, since the ARGB32
+        // XRender picture format consumes premultiplied data.
+        let premultiplied = cursor.0.premultiplied;
+        for frame in &mut cursor.0.frames {
+            prepare_frame_rgba(&mut frame.rgba, premultiplied);
+        }
+
+        let cursor = event_loop.xconn.create_cursor_from_frames(&cursor.0.frames)?;
+
+        Ok(Self { inner: Arc::new(CustomCursorInner { xconn: event_loop.xconn.clone(), cursor }) })
+    }
 
-        Self { inner: Arc::new(CustomCursorInner { xconn: event_loop.xconn.clone(), cursor }) }
+    /// A placeholder holding `XCB_NONE` instead of a server-side cursor, used as a last-resort
+    /// fallback when `try_new` fails.
+    ///
+    /// This deliberately does not round-trip to the server: `try_new` can fail precisely because
+    /// the connection is gone or the ARGB32 format is missing, so building the fallback out of a
+    /// fresh `create_cursor_from_image` call would just panic or fail on the same condition.
+    /// `XCB_NONE` is the protocol's `None` cursor: a window given it shows its parent window's
+    /// cursor rather than one of its own.
+    fn empty(event_loop: &ActiveEventLoop) -> CustomCursor {
+        const XCB_NONE: xproto::Cursor = 0;
+        Self {
+            inner: Arc::new(CustomCursorInner {
+                xconn: event_loop.xconn.clone(),
+                cursor: XCB_NONE,
+            }),
+        }
     }
 }
 
@@ -218,9 +353,322 @@ impl Default for SelectedCursor {
     }
 }
 
+/// A single frame of a (possibly animated) cursor, backed either by application-supplied
+/// [`CustomCursorFrame`]s or by an image decoded from an on-disk XCursor file.
+trait CursorFrameImage {
+    fn width(&self) -> u16;
+    fn height(&self) -> u16;
+    fn hotspot(&self) -> (u16, u16);
+    fn delay(&self) -> u32;
+    /// Premultiplied BGRA bytes, ready for [`XConnection::create_cursor_from_image`].
+    fn argb(&self) -> &[u8];
+}
+
+impl CursorFrameImage for CustomCursorFrame {
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    fn hotspot(&self) -> (u16, u16) {
+        (self.hotspot_x, self.hotspot_y)
+    }
+
+    fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    fn argb(&self) -> &[u8] {
+        &self.rgba
+    }
+}
+
+impl CursorFrameImage for xcursor::Image {
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    fn hotspot(&self) -> (u16, u16) {
+        (self.xhot, self.yhot)
+    }
+
+    fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    fn argb(&self) -> &[u8] {
+        // XCursor pixels are already stored as premultiplied, little-endian ARGB32 words, which
+        // is exactly the byte layout the ARGB32 picture format expects.
+        &self.pixels
+    }
+}
+
+/// Reorder an RGBA buffer to BGRA in place, premultiplying by alpha unless the caller has
+/// already done so.
+///
+/// The ARGB32 picture format used by [`XConnection::create_cursor_from_image`] expects
+/// premultiplied alpha, matching how `wayland-cursor` and other XCursor loaders prepare their
+/// image data; feeding it straight (non-premultiplied) RGBA produces visible halos around
+/// semi-transparent pixels.
+fn prepare_frame_rgba(rgba: &mut [u8], premultiplied: bool) {
+    rgba.chunks_mut(4).for_each(|chunk| {
+        let [r, g, b, a] = [chunk[0], chunk[1], chunk[2], chunk[3]];
+        if premultiplied {
+            chunk.copy_from_slice(&[b, g, r, a]);
+        } else {
+            let premultiply = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+            chunk.copy_from_slice(&[premultiply(b), premultiply(g), premultiply(r), a]);
+        }
+    });
+}
+
 struct CallOnDrop<F: FnMut()>(F);
 impl<F: FnMut()> Drop for CallOnDrop<F> {
     fn drop(&mut self) {
         (self.0)();
     }
 }
+
+/// A minimal reader for the on-disk [XCursor file format][spec], used so that animated and
+/// size-negotiated cursors can be loaded without going through `x11rb`'s `cursor::Handle`, which
+/// only ever returns a single, fixed-size glyph.
+///
+/// [spec]: https://www.x.org/releases/X11R7.7/doc/man/man3/Xcursor.3.xhtml
+mod xcursor {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const MAGIC: u32 = 0x72756358; // "Xcur" as a little-endian u32.
+    const IMAGE_CHUNK_TYPE: u32 = 0xfffd0002;
+
+    /// A single decoded cursor image, ready to be uploaded as an X pixmap.
+    pub(super) struct Image {
+        pub(super) width: u16,
+        pub(super) height: u16,
+        pub(super) xhot: u16,
+        pub(super) yhot: u16,
+        pub(super) delay: u32,
+        pub(super) pixels: Vec<u8>,
+    }
+
+    /// The theme named by `XCURSOR_THEME`, falling back to `"default"`.
+    pub(super) fn theme_name() -> String {
+        env::var("XCURSOR_THEME").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "default".into())
+    }
+
+    /// The nominal cursor size named by `XCURSOR_SIZE`, falling back to `24`.
+    pub(super) fn target_size() -> u32 {
+        env::var("XCURSOR_SIZE").ok().and_then(|s| s.parse().ok()).unwrap_or(24)
+    }
+
+    /// Load every frame of `name` from `theme` (or the themes it inherits from), picking the
+    /// nominal size closest to `target_size`.
+    pub(super) fn load_icon(theme: &str, name: &str, target_size: u32) -> Option<Vec<Image>> {
+        let mut seen_themes = Vec::new();
+        load_icon_inner(theme, name, target_size, &mut seen_themes)
+    }
+
+    fn load_icon_inner(
+        theme: &str,
+        name: &str,
+        target_size: u32,
+        seen_themes: &mut Vec<String>,
+    ) -> Option<Vec<Image>> {
+        if seen_themes.iter().any(|seen| seen == theme) {
+            return None; // Broken `Inherits` cycle.
+        }
+        seen_themes.push(theme.to_owned());
+
+        for base in search_paths() {
+            let file = base.join(theme).join("cursors").join(name);
+            if let Ok(bytes) = fs::read(&file) {
+                if let Some(frames) = parse_and_select(&bytes, target_size) {
+                    return Some(frames);
+                }
+            }
+        }
+
+        for parent in inherited_themes(theme) {
+            if let Some(frames) = load_icon_inner(&parent, name, target_size, seen_themes) {
+                return Some(frames);
+            }
+        }
+
+        None
+    }
+
+    /// Directories that may contain icon themes, in search order: `XCURSOR_PATH` if set,
+    /// otherwise the usual XDG icon locations.
+    fn search_paths() -> Vec<PathBuf> {
+        if let Ok(path) = env::var("XCURSOR_PATH") {
+            return env::split_paths(&path).map(PathBuf::from).collect();
+        }
+
+        let home = env::var("HOME").map(PathBuf::from).ok();
+        let mut paths = Vec::new();
+        if let Some(home) = &home {
+            paths.push(home.join(".local/share/icons"));
+            paths.push(home.join(".icons"));
+        }
+        if let Ok(xdg_data_dirs) = env::var("XDG_DATA_DIRS") {
+            paths.extend(env::split_paths(&xdg_data_dirs).map(|p| p.join("icons")));
+        } else {
+            paths.push(PathBuf::from("/usr/local/share/icons"));
+            paths.push(PathBuf::from("/usr/share/icons"));
+        }
+        paths.push(PathBuf::from("/usr/share/pixmaps"));
+        paths
+    }
+
+    /// The themes listed in `theme`'s `index.theme` `Inherits=` key, in order.
+    fn inherited_themes(theme: &str) -> Vec<String> {
+        for base in search_paths() {
+            let index = base.join(theme).join("index.theme");
+            let Ok(contents) = fs::read_to_string(&index) else { continue };
+            for line in contents.lines() {
+                let Some(value) = line.strip_prefix("Inherits=") else { continue };
+                return value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+        }
+        Vec::new()
+    }
+
+    /// Parse an XCursor file and select the group of frames whose nominal size is nearest
+    /// `target_size`, biasing towards the largest available on ties or when `target_size` is
+    /// larger than anything on offer.
+    fn parse_and_select(bytes: &[u8], target_size: u32) -> Option<Vec<Image>> {
+        let images = parse(bytes)?;
+
+        let mut by_size: Vec<(u32, Vec<Image>)> = Vec::new();
+        for (size, image) in images {
+            match by_size.iter_mut().find(|(s, _)| *s == size) {
+                Some((_, frames)) => frames.push(image),
+                None => by_size.push((size, vec![image])),
+            }
+        }
+
+        by_size
+            .into_iter()
+            .min_by_key(|(size, _)| {
+                let diff = (*size as i64 - target_size as i64).abs();
+                // Prefer the larger size on a tie by nudging it ahead in sort order.
+                (diff, u32::MAX - *size)
+            })
+            .map(|(_, frames)| frames)
+    }
+
+    /// Parse every image chunk out of an XCursor file, paired with its nominal size.
+    fn parse(bytes: &[u8]) -> Option<Vec<(u32, Image)>> {
+        let header_size = read_u32(bytes, 4)? as usize;
+        let magic = read_u32(bytes, 0)?;
+        if magic != MAGIC {
+            return None;
+        }
+        let ntoc = read_u32(bytes, 12)? as usize;
+
+        let mut images = Vec::new();
+        for i in 0..ntoc {
+            let entry = header_size + i * 12;
+            let chunk_type = read_u32(bytes, entry)?;
+            let subtype = read_u32(bytes, entry + 4)?;
+            let position = read_u32(bytes, entry + 8)? as usize;
+
+            if chunk_type != IMAGE_CHUNK_TYPE {
+                continue;
+            }
+
+            // Each image chunk starts with a 16-byte chunk header (header size, type, subtype,
+            // version) before its own fields.
+            let width = read_u32(bytes, position + 16)?;
+            let height = read_u32(bytes, position + 20)?;
+            let xhot = read_u32(bytes, position + 24)?;
+            let yhot = read_u32(bytes, position + 28)?;
+            let delay = read_u32(bytes, position + 32)?;
+
+            let pixel_count = (width as usize).checked_mul(height as usize)?;
+            let pixels_start = position + 36;
+            let pixels_end = pixels_start.checked_add(pixel_count.checked_mul(4)?)?;
+            let pixels = bytes.get(pixels_start..pixels_end)?.to_vec();
+
+            images.push((subtype, Image {
+                width: width as u16,
+                height: height as u16,
+                xhot: xhot as u16,
+                yhot: yhot as u16,
+                delay,
+                pixels,
+            }));
+        }
+
+        (!images.is_empty()).then_some(images)
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+        bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        /// Hand-builds the smallest possible XCursor file: a file header, one TOC entry, and
+        /// one 1x1 image chunk, laid out exactly like a real `watch` cursor would be.
+        fn sample_cursor_bytes() -> Vec<u8> {
+            let mut bytes = Vec::new();
+            push_u32(&mut bytes, MAGIC);
+            push_u32(&mut bytes, 16); // file header size
+            push_u32(&mut bytes, 0x1_0000); // file version
+            push_u32(&mut bytes, 1); // ntoc
+
+            let position = 16 + 12; // header + one TOC entry
+            push_u32(&mut bytes, IMAGE_CHUNK_TYPE);
+            push_u32(&mut bytes, 32); // nominal size
+            push_u32(&mut bytes, position as u32);
+
+            push_u32(&mut bytes, 36); // chunk header size
+            push_u32(&mut bytes, IMAGE_CHUNK_TYPE);
+            push_u32(&mut bytes, 32); // nominal size
+            push_u32(&mut bytes, 1); // chunk version
+            push_u32(&mut bytes, 1); // width
+            push_u32(&mut bytes, 1); // height
+            push_u32(&mut bytes, 2); // xhot
+            push_u32(&mut bytes, 3); // yhot
+            push_u32(&mut bytes, 100); // delay
+            bytes.extend_from_slice(&[0xaa, 0xbb, 0xcc, 0xdd]); // one ARGB32 pixel
+
+            bytes
+        }
+
+        #[test]
+        fn parses_image_chunk_fields_at_correct_offsets() {
+            let images = parse(&sample_cursor_bytes()).expect("valid cursor file");
+            assert_eq!(images.len(), 1);
+
+            let (size, image) = &images[0];
+            assert_eq!(*size, 32);
+            assert_eq!(image.width, 1);
+            assert_eq!(image.height, 1);
+            assert_eq!(image.xhot, 2);
+            assert_eq!(image.yhot, 3);
+            assert_eq!(image.delay, 100);
+            assert_eq!(image.pixels, [0xaa, 0xbb, 0xcc, 0xdd]);
+        }
+    }
+}
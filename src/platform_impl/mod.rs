@@ -0,0 +1,10 @@
+mod linux;
+
+pub(crate) use linux::*;
+
+/// Per-backend wrapper around [`crate::cursor::CustomCursorSource`], so each platform's
+/// `CustomCursor::new` takes a single, backend-specific parameter type even though the payload
+/// it carries is shared across platforms.
+pub(crate) struct PlatformCustomCursorSource(pub(crate) crate::cursor::CustomCursorSource);
+
+pub(crate) use crate::cursor::CustomCursorFrame;